@@ -1,21 +1,30 @@
-use anyhow::{bail, Result};
-use serde::Deserialize;
+use anyhow::{anyhow, bail, Result};
+use async_channel::{Receiver, Sender};
+use bytes::Bytes;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
 use turbo_tasks::{
     primitives::{JsonValueVc, StringsVc},
+    util::SharedError,
     Value,
 };
-use turbo_tasks_fs::{json::parse_json_rope_with_source_context, to_sys_path, FileSystemPathVc};
+use turbo_tasks_fs::{
+    json::parse_json_rope_with_source_context, to_sys_path, DirectoryContent, DirectoryEntry,
+    FileSystemEntryType, FileSystemPathVc,
+};
 use turbopack::evaluate_context::node_evaluate_asset_context;
 use turbopack_core::{
     asset::AssetVc,
     context::{AssetContext, AssetContextVc},
+    reference_type::{EntryReferenceSubType, ReferenceType},
     resolve::{find_context_file, FindContextFileResult},
     source_asset::SourceAssetVc,
 };
 use turbopack_dev_server::source::{headers::Headers, query::Query};
 use turbopack_ecmascript::{
-    chunk::EcmascriptChunkPlaceablesVc, EcmascriptInputTransform, EcmascriptInputTransformsVc,
-    EcmascriptModuleAssetType, EcmascriptModuleAssetVc,
+    chunk::{EcmascriptChunkPlaceableVc, EcmascriptChunkPlaceablesVc},
+    EcmascriptInputTransform, EcmascriptInputTransformsVc, EcmascriptModuleAssetType,
+    EcmascriptModuleAssetVc,
 };
 use turbopack_node::{
     evaluate::{evaluate, JavaScriptValue},
@@ -75,10 +84,39 @@ pub struct MiddlewareHeadersResponse {
     pub headers: Vec<String>,
 }
 
+/// Filenames (across `page_extensions`) that App Router segments are built from, analogous to
+/// [`middleware_files`] for the middleware entry point. `layout`/`default` alone don't make a
+/// segment routable; see [`match_app_route`] for which subset decides that.
+const APP_SEGMENT_FILE_PREFIXES: [&str; 4] = ["page.", "route.", "layout.", "default."];
+
+/// A single dynamic segment's matched value: one path component for `[id]`, several for
+/// `[...slug]`/`[[...slug]]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ParamValue {
+    Single(String),
+    Multi(Vec<String>),
+}
+
+/// A request successfully matched against a route pattern, with the extracted dynamic params
+/// keyed by segment name so callers don't need to re-parse the URL.
+#[turbo_tasks::value(shared)]
+#[derive(Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteMatch {
+    pub matched_route: String,
+    pub params: IndexMap<String, ParamValue>,
+}
+
 #[turbo_tasks::value(shared)]
 #[derive(Debug, Clone, Default)]
 pub struct MiddlewareBodyResponse(pub Vec<u8>);
 
+/// Body chunks of a streamed [`RouterResult::StreamingMiddleware`] response, in arrival order.
+/// Cloning shares the same underlying queue, since the `route()` caller and the stream consumer
+/// both need a handle to it.
+pub type MiddlewareResponseBody = Receiver<Result<Bytes, SharedError>>;
+
 #[turbo_tasks::value(shared)]
 #[derive(Debug, Clone, Default)]
 pub struct FullMiddlewareResponse {
@@ -92,13 +130,9 @@ enum RouterIncomingMessage {
     Rewrite {
         data: RewriteResponse,
     },
-    // TODO: Implement
-    #[allow(dead_code)]
     MiddlewareHeaders {
         data: MiddlewareHeadersResponse,
     },
-    // TODO: Implement
-    #[allow(dead_code)]
     MiddlewareBody {
         data: MiddlewareBodyResponse,
     },
@@ -109,13 +143,36 @@ enum RouterIncomingMessage {
 }
 
 #[derive(Debug)]
-#[turbo_tasks::value]
+#[turbo_tasks::value(eq = "manual", serialization = "none")]
 pub enum RouterResult {
     Rewrite(RewriteResponse),
     FullMiddleware(FullMiddlewareResponse),
+    /// A middleware response whose body is still being produced. `headers` is always available
+    /// up front; `body` yields chunks as the middleware (or the upstream it proxies) flushes
+    /// them, instead of buffering the whole response like [`RouterResult::FullMiddleware`].
+    StreamingMiddleware(MiddlewareHeadersResponse, MiddlewareResponseBody),
+    /// A request resolved against the App Router's `app/` segment tree, coexisting with
+    /// `Rewrite`/`FullMiddleware`/`StreamingMiddleware` so a single `route()` call can report
+    /// whether app, pages, or middleware should handle the request.
+    AppRoute(RouteMatch),
     Error,
 }
 
+impl PartialEq for RouterResult {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Rewrite(a), Self::Rewrite(b)) => a == b,
+            (Self::FullMiddleware(a), Self::FullMiddleware(b)) => a == b,
+            (Self::StreamingMiddleware(a, _), Self::StreamingMiddleware(b, _)) => a == b,
+            (Self::AppRoute(a), Self::AppRoute(b)) => a == b,
+            (Self::Error, Self::Error) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for RouterResult {}
+
 impl From<RouterIncomingMessage> for RouterResult {
     fn from(value: RouterIncomingMessage) -> Self {
         match value {
@@ -126,6 +183,374 @@ impl From<RouterIncomingMessage> for RouterResult {
     }
 }
 
+/// Drains a streamed router response: the first message must be `MiddlewareHeaders`, after which
+/// subsequent `MiddlewareBody` messages are forwarded to the returned body channel in order. A
+/// malformed/missing headers message, or anything other than a body message following it
+/// (including an `Error`), ends the stream rather than panicking.
+async fn consume_middleware_stream(
+    incoming: Receiver<Result<Bytes, SharedError>>,
+) -> Result<RouterResult> {
+    let Ok(first) = incoming.recv().await else {
+        return Ok(RouterResult::Error);
+    };
+    let message: RouterIncomingMessage = match first {
+        Ok(bytes) => match serde_json::from_slice(&bytes) {
+            Ok(message) => message,
+            Err(_) => return Ok(RouterResult::Error),
+        },
+        Err(_) => return Ok(RouterResult::Error),
+    };
+    let headers = match message {
+        RouterIncomingMessage::MiddlewareHeaders { data } => data,
+        _ => return Ok(RouterResult::Error),
+    };
+
+    let (body_tx, body_rx): (Sender<Result<Bytes, SharedError>>, _) = async_channel::unbounded();
+    tokio::spawn(forward_middleware_body(incoming, body_tx));
+
+    Ok(RouterResult::StreamingMiddleware(headers, body_rx))
+}
+
+/// Forwards `MiddlewareBody` chunks from `incoming` to `body_tx` in order. `incoming` closing
+/// (the sender side finishing normally) ends the body cleanly. Anything else that cuts the
+/// stream short — a propagated error, a message that doesn't parse, or an explicit `Error`
+/// message — is itself forwarded through `body_tx` so the consumer can tell a truncated body
+/// from a complete one, rather than the channel just closing either way.
+async fn forward_middleware_body(
+    incoming: Receiver<Result<Bytes, SharedError>>,
+    body_tx: Sender<Result<Bytes, SharedError>>,
+) {
+    while let Ok(chunk) = incoming.recv().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                let _ = body_tx.send(Err(err)).await;
+                break;
+            }
+        };
+        let message = match serde_json::from_slice(&chunk) {
+            Ok(message) => message,
+            Err(err) => {
+                let _ = body_tx.send(Err(SharedError::new(err.into()))).await;
+                break;
+            }
+        };
+        match message {
+            RouterIncomingMessage::MiddlewareBody { data } => {
+                if body_tx.send(Ok(Bytes::from(data.0))).await.is_err() {
+                    break;
+                }
+            }
+            RouterIncomingMessage::Error(err) => {
+                let _ = body_tx
+                    .send(Err(SharedError::new(anyhow!(
+                        "middleware stream ended with an error: {err:?}"
+                    ))))
+                    .await;
+                break;
+            }
+            _ => {
+                let _ = body_tx
+                    .send(Err(SharedError::new(anyhow!(
+                        "unexpected message while streaming middleware body"
+                    ))))
+                    .await;
+                break;
+            }
+        }
+    }
+}
+
+/// The project's `app/` directory, preferring `src/app/` when both exist (mirrors the
+/// `pages`/`src/pages` convention).
+async fn find_app_dir(project_path: FileSystemPathVc) -> Result<Option<FileSystemPathVc>> {
+    for candidate in ["src/app", "app"] {
+        let path = project_path.join(candidate);
+        if matches!(&*path.get_type().await?, FileSystemEntryType::Directory) {
+            return Ok(Some(path));
+        }
+    }
+    Ok(None)
+}
+
+/// Route group directories (`(name)`) are transparent to the URL, so a path segment may be
+/// matched by any directory reachable from `dir` by descending through nothing but groups.
+async fn group_dirs(dir: FileSystemPathVc) -> Result<Vec<FileSystemPathVc>> {
+    let mut result = vec![dir];
+    let mut frontier = vec![dir];
+    while let Some(current) = frontier.pop() {
+        let DirectoryContent::Entries(entries) = &*current.read_dir().await? else {
+            continue;
+        };
+        for entry in entries.values() {
+            if let DirectoryEntry::Directory(child) = entry {
+                let child_path = child.await?;
+                let name = child_path.file_name();
+                if name.starts_with('(') && name.ends_with(')') {
+                    result.push(*child);
+                    frontier.push(*child);
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// A single segment of an on-disk route pattern, classified from its folder name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SegmentMatcher {
+    Static(String),
+    Dynamic(String),
+    CatchAll(String),
+    OptionalCatchAll(String),
+}
+
+impl SegmentMatcher {
+    fn parse(name: &str) -> Self {
+        if let Some(inner) = name.strip_prefix("[[").and_then(|rest| rest.strip_suffix("]]")) {
+            let param = inner.strip_prefix("...").unwrap_or(inner);
+            SegmentMatcher::OptionalCatchAll(param.to_owned())
+        } else if let Some(inner) = name.strip_prefix('[').and_then(|rest| rest.strip_suffix(']'))
+        {
+            match inner.strip_prefix("...") {
+                Some(param) => SegmentMatcher::CatchAll(param.to_owned()),
+                None => SegmentMatcher::Dynamic(inner.to_owned()),
+            }
+        } else {
+            SegmentMatcher::Static(name.to_owned())
+        }
+    }
+}
+
+/// Ranks candidate child segments by App Router specificity and returns the most specific one.
+/// Static names are skipped (they're matched by exact lookup before this runs), so among the
+/// dynamic family the order is dynamic > catch-all > optional-catch-all.
+fn pick_best_dynamic_match<'a, T>(
+    candidates: impl IntoIterator<Item = (&'a str, T)>,
+) -> Option<(SegmentMatcher, T)> {
+    let mut best: Option<(SegmentMatcher, T)> = None;
+    for (name, value) in candidates {
+        let matcher = SegmentMatcher::parse(name);
+        let rank = match matcher {
+            SegmentMatcher::Static(_) => continue,
+            SegmentMatcher::Dynamic(_) => 0,
+            SegmentMatcher::CatchAll(_) => 1,
+            SegmentMatcher::OptionalCatchAll(_) => 2,
+        };
+        let best_rank = best.as_ref().map(|(m, _)| match m {
+            SegmentMatcher::Dynamic(_) => 0,
+            SegmentMatcher::CatchAll(_) => 1,
+            _ => 2,
+        });
+        if best_rank.map_or(true, |best_rank| rank < best_rank) {
+            best = Some((matcher, value));
+        }
+    }
+    best
+}
+
+/// Splits a pathname into its non-empty URL segments, ignoring leading/trailing slashes.
+fn split_pathname(pathname: &str) -> Vec<&str> {
+    pathname
+        .trim_matches('/')
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect()
+}
+
+/// Whether `dir` itself (not an ancestor) defines one of the routable App Router segment files.
+/// This deliberately checks `dir`'s own directory listing rather than `find_context_file`, which
+/// walks *up* through ancestors — that's right for resolving `next.config.*`/`middleware.*` from
+/// an arbitrary starting point, but wrong here: a leaf segment with only a `layout.tsx` must not
+/// be reported as routable just because some ancestor (e.g. the app root) has a `page.tsx`.
+async fn dir_is_routable(dir: FileSystemPathVc, extensions: &[String]) -> Result<bool> {
+    let DirectoryContent::Entries(entries) = &*dir.read_dir().await? else {
+        return Ok(false);
+    };
+    for prefix in APP_SEGMENT_FILE_PREFIXES {
+        // A bare `layout`/`default` doesn't make the segment itself routable: `layout` only
+        // wraps children, and `default` only matters as a parallel-route (`@slot`) fallback.
+        if prefix == "layout." || prefix == "default." {
+            continue;
+        }
+        for ext in extensions {
+            if matches!(
+                entries.get(&format!("{prefix}{ext}")),
+                Some(DirectoryEntry::File(_))
+            ) {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Once the URL is exhausted, either one of `candidates` is directly routable, or an optional
+/// catch-all directory one level down matches the parent path with zero segments.
+async fn match_terminal(
+    candidates: &[FileSystemPathVc],
+    extensions: &[String],
+    pattern_segments: &[String],
+    params: &IndexMap<String, ParamValue>,
+) -> Result<Option<RouteMatch>> {
+    for dir in candidates {
+        if dir_is_routable(*dir, extensions).await? {
+            return Ok(Some(RouteMatch {
+                matched_route: format!("/{}", pattern_segments.join("/")),
+                params: params.clone(),
+            }));
+        }
+        let DirectoryContent::Entries(entries) = &*dir.read_dir().await? else {
+            continue;
+        };
+        for (name, entry) in entries.iter() {
+            let DirectoryEntry::Directory(child) = entry else {
+                continue;
+            };
+            let SegmentMatcher::OptionalCatchAll(param) = SegmentMatcher::parse(name) else {
+                continue;
+            };
+            if dir_is_routable(*child, extensions).await? {
+                let mut params = params.clone();
+                params.insert(param.clone(), ParamValue::Multi(Vec::new()));
+                let mut pattern_segments = pattern_segments.to_vec();
+                pattern_segments.push(format!("[[...{param}]]"));
+                return Ok(Some(RouteMatch {
+                    matched_route: format!("/{}", pattern_segments.join("/")),
+                    params,
+                }));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Matches `remaining` against the App Router segment tree, at each depth preferring a static
+/// child directory over a single dynamic (`[param]`) one, which in turn takes priority over a
+/// catch-all (`[...param]`), with an optional catch-all (`[[...param]]`) as the lowest-priority
+/// fallback. A static branch that dead-ends (no routable file anywhere further down) backtracks
+/// to try the next static candidate and, failing all of those, the dynamic family at this same
+/// depth, rather than reporting a miss just because the first/most-specific branch failed.
+async fn match_segment(
+    candidates: Vec<FileSystemPathVc>,
+    extensions: &[String],
+    remaining: &[&str],
+    pattern_segments: &[String],
+    params: &IndexMap<String, ParamValue>,
+) -> Result<Option<RouteMatch>> {
+    let Some((&segment, rest)) = remaining.split_first() else {
+        return match_terminal(&candidates, extensions, pattern_segments, params).await;
+    };
+
+    for dir in &candidates {
+        let DirectoryContent::Entries(entries) = &*dir.read_dir().await? else {
+            continue;
+        };
+        if let Some(DirectoryEntry::Directory(child)) = entries.get(segment) {
+            let mut next_pattern = pattern_segments.to_vec();
+            next_pattern.push(segment.to_owned());
+            let child_candidates = group_dirs(*child).await?;
+            let found = Box::pin(match_segment(
+                child_candidates,
+                extensions,
+                rest,
+                &next_pattern,
+                params,
+            ))
+            .await?;
+            if found.is_some() {
+                return Ok(found);
+            }
+        }
+    }
+
+    // Every static branch at this depth either doesn't exist or dead-ended further down: fall
+    // back to the dynamic/catch-all/optional-catch-all sibling, in that priority order, across
+    // every group-transparent candidate directory.
+    let mut dynamic_children: Vec<(String, FileSystemPathVc)> = Vec::new();
+    for dir in &candidates {
+        let DirectoryContent::Entries(entries) = &*dir.read_dir().await? else {
+            continue;
+        };
+        for (name, entry) in entries.iter() {
+            if let DirectoryEntry::Directory(child) = entry {
+                dynamic_children.push((name.clone(), *child));
+            }
+        }
+    }
+    let best = pick_best_dynamic_match(
+        dynamic_children
+            .iter()
+            .map(|(name, child)| (name.as_str(), *child)),
+    );
+
+    match best {
+        Some((SegmentMatcher::Dynamic(param), child)) => {
+            let mut next_params = params.clone();
+            next_params.insert(param.clone(), ParamValue::Single(segment.to_owned()));
+            let mut next_pattern = pattern_segments.to_vec();
+            next_pattern.push(format!("[{param}]"));
+            let child_candidates = group_dirs(child).await?;
+            Box::pin(match_segment(
+                child_candidates,
+                extensions,
+                rest,
+                &next_pattern,
+                &next_params,
+            ))
+            .await
+        }
+        Some((SegmentMatcher::CatchAll(param), child)) => {
+            let mut next_params = params.clone();
+            next_params.insert(
+                param.clone(),
+                ParamValue::Multi(remaining.iter().map(|s| (*s).to_owned()).collect()),
+            );
+            let mut next_pattern = pattern_segments.to_vec();
+            next_pattern.push(format!("[...{param}]"));
+            let child_candidates = group_dirs(child).await?;
+            match_terminal(&child_candidates, extensions, &next_pattern, &next_params).await
+        }
+        Some((SegmentMatcher::OptionalCatchAll(param), child)) => {
+            let mut next_params = params.clone();
+            next_params.insert(
+                param.clone(),
+                ParamValue::Multi(remaining.iter().map(|s| (*s).to_owned()).collect()),
+            );
+            let mut next_pattern = pattern_segments.to_vec();
+            next_pattern.push(format!("[[...{param}]]"));
+            let child_candidates = group_dirs(child).await?;
+            match_terminal(&child_candidates, extensions, &next_pattern, &next_params).await
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Matches `pathname` against the App Router segment tree rooted at `app_dir`. See
+/// [`match_segment`] for the priority/backtracking rules. Returns `None` when no directory that
+/// the path resolves to defines a `page`/`route` entry file.
+async fn match_app_route(
+    app_dir: FileSystemPathVc,
+    extensions: &[String],
+    pathname: &str,
+) -> Result<Option<RouteMatch>> {
+    let url_segments = split_pathname(pathname);
+    let candidates = group_dirs(app_dir).await?;
+    match_segment(candidates, extensions, &url_segments, &[], &IndexMap::new()).await
+}
+
+async fn match_app_request(
+    project_path: FileSystemPathVc,
+    page_extensions: StringsVc,
+    pathname: &str,
+) -> Result<Option<RouteMatch>> {
+    let Some(app_dir) = find_app_dir(project_path).await? else {
+        return Ok(None);
+    };
+    let extensions = page_extensions.await?;
+    match_app_route(app_dir, &extensions, pathname).await
+}
+
 #[turbo_tasks::function]
 async fn extra_config(
     context: AssetContextVc,
@@ -152,6 +577,62 @@ async fn extra_config(
     Ok(EcmascriptChunkPlaceablesVc::cell(vec![config_chunk]))
 }
 
+/// Resolves `middleware_files(page_extensions)` against `project_path` and processes the match
+/// through `context`, so middleware gets a real module graph (imports, transforms, transitive
+/// dependencies) instead of being executed opaquely by `router.js`.
+#[turbo_tasks::function]
+async fn middleware_module(
+    context: AssetContextVc,
+    project_path: FileSystemPathVc,
+    page_extensions: StringsVc,
+) -> Result<EcmascriptChunkPlaceablesVc> {
+    let find_config_result = find_context_file(project_path, middleware_files(page_extensions));
+    let middleware_asset = match &*find_config_result.await? {
+        FindContextFileResult::Found(middleware_path, _) => {
+            Some(SourceAssetVc::new(*middleware_path))
+        }
+        FindContextFileResult::NotFound(_) => None,
+    };
+    let Some(middleware_asset) = middleware_asset else {
+        return Ok(EcmascriptChunkPlaceablesVc::empty());
+    };
+
+    let processed = context.process(
+        middleware_asset.into(),
+        Value::new(ReferenceType::Entry(EntryReferenceSubType::Middleware)),
+    );
+    let Some(middleware_module) = EcmascriptChunkPlaceableVc::resolve_from(processed).await?
+    else {
+        bail!("middleware module is not placeable into an ecmascript chunk");
+    };
+    Ok(EcmascriptChunkPlaceablesVc::cell(vec![middleware_module]))
+}
+
+/// The on-disk path of the processed middleware module's chunk, if middleware exists. Handed to
+/// the router entry as an explicit argument (see `route()`) so it can `require()` this module
+/// directly instead of re-resolving `middleware.*` itself.
+///
+/// CAVEAT (open question, not resolved in this tree): `module.ident().path()` is the module's
+/// logical/source identity path, not necessarily the path `evaluate()` actually writes the
+/// compiled chunk to on disk. Those two line up for the other `extra_configs` placeables (whose
+/// identity path is also where `evaluate()`'s Node.js-filesystem chunking writes them), but that
+/// has not been confirmed against `evaluate()`'s placeable-to-chunk-path handling in
+/// `turbopack_node`, whose source isn't part of this crate. If they diverge, `router.js`'s
+/// `require()` of this path will fail at runtime; a `None` value (no middleware present) is
+/// unaffected either way.
+async fn middleware_chunk_path(
+    context: AssetContextVc,
+    project_path: FileSystemPathVc,
+    page_extensions: StringsVc,
+) -> Result<Option<String>> {
+    let modules = middleware_module(context, project_path, page_extensions).await?;
+    let Some(module) = modules.first() else {
+        return Ok(None);
+    };
+    let path = module.ident().path().await?;
+    Ok(Some(path.path.clone()))
+}
+
 #[turbo_tasks::function]
 async fn extra_configs(
     context: AssetContextVc,
@@ -159,11 +640,10 @@ async fn extra_configs(
     page_extensions: StringsVc,
 ) -> Result<EcmascriptChunkPlaceablesVc> {
     let next_config = extra_config(context, project_path, next_configs()).await?;
-    let middleware_config =
-        extra_config(context, project_path, middleware_files(page_extensions)).await?;
+    let middleware_module = middleware_module(context, project_path, page_extensions).await?;
 
     let mut concat = next_config.clone_value();
-    concat.extend(&*middleware_config);
+    concat.extend(&*middleware_module);
     Ok(EcmascriptChunkPlaceablesVc::cell(concat))
 }
 
@@ -192,10 +672,16 @@ pub async fn route(
     let project_path = wrap_with_next_js_fs(project_root);
     let context = node_evaluate_asset_context(Some(get_next_build_import_map(project_path)));
     let router_asset = route_executor(context, project_path);
-    // TODO this is a hack to get these files watched.
+    // Resolved next.config.* plus the processed middleware module, enrolled here purely so both
+    // are watched and carried through the transform pipeline alongside `router_asset`. The
+    // middleware module's actual *path* is handed to `router.js` below as a request argument, so
+    // it can `require()` the already-processed module instead of re-resolving `middleware.*`.
     let extra_configs = extra_configs(context, project_path, next_config.page_extensions());
+    let middleware_chunk_path =
+        middleware_chunk_path(context, project_path, next_config.page_extensions()).await?;
 
-    let request = serde_json::value::to_value(&*request.await?)?;
+    let request_value = request.await?;
+    let request_json = serde_json::value::to_value(&*request_value)?;
     let Some(dir) = to_sys_path(project_root).await? else {
         bail!("Next.js requires a disk path to check for valid routes");
     };
@@ -208,21 +694,219 @@ pub async fn route(
         intermediate_output_path.join("router"),
         Some(extra_configs),
         vec![
-            JsonValueVc::cell(request),
+            JsonValueVc::cell(request_json),
             JsonValueVc::cell(dir.to_string_lossy().into()),
+            JsonValueVc::cell(serde_json::to_value(&middleware_chunk_path)?),
         ],
         false,
     )
     .await?;
 
-    match &*result {
+    let result = match &*result {
         JavaScriptValue::Value(val) => {
             let result: RouterIncomingMessage = parse_json_rope_with_source_context(val)?;
-            Ok(RouterResult::from(result).cell())
+            RouterResult::from(result)
         }
-        JavaScriptValue::Error => Ok(RouterResult::Error.cell()),
-        JavaScriptValue::Stream(_) => {
-            unimplemented!("Stream not supported now");
+        JavaScriptValue::Error => RouterResult::Error,
+        JavaScriptValue::Stream(stream) => consume_middleware_stream(stream.clone()).await?,
+    };
+
+    // `router.js` only knows about pages and middleware, so it reports `Error` when neither
+    // claims the request. Middleware has already run for every request above; only now, once
+    // it's clear pages/middleware didn't handle this one, do we fall back to the App Router.
+    if matches!(result, RouterResult::Error) {
+        if let Some(app_match) = match_app_request(
+            project_path,
+            next_config.page_extensions(),
+            &request_value.pathname,
+        )
+        .await?
+        {
+            return Ok(RouterResult::AppRoute(app_match).cell());
         }
     }
+
+    Ok(result.cell())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segment_matcher_parse_classifies_folder_names() {
+        assert_eq!(
+            SegmentMatcher::parse("blog"),
+            SegmentMatcher::Static("blog".into())
+        );
+        assert_eq!(
+            SegmentMatcher::parse("[id]"),
+            SegmentMatcher::Dynamic("id".into())
+        );
+        assert_eq!(
+            SegmentMatcher::parse("[...slug]"),
+            SegmentMatcher::CatchAll("slug".into())
+        );
+        assert_eq!(
+            SegmentMatcher::parse("[[...slug]]"),
+            SegmentMatcher::OptionalCatchAll("slug".into())
+        );
+    }
+
+    #[test]
+    fn pick_best_dynamic_match_prefers_dynamic_over_catch_all_variants() {
+        let candidates = vec![
+            ("[[...slug]]", "optional-catch-all"),
+            ("[...slug]", "catch-all"),
+            ("[id]", "dynamic"),
+        ];
+        let (matcher, value) = pick_best_dynamic_match(candidates).unwrap();
+        assert_eq!(matcher, SegmentMatcher::Dynamic("id".into()));
+        assert_eq!(value, "dynamic");
+    }
+
+    #[test]
+    fn pick_best_dynamic_match_prefers_catch_all_over_optional_catch_all() {
+        let candidates = vec![("[[...slug]]", "optional-catch-all"), ("[...slug]", "catch-all")];
+        let (matcher, value) = pick_best_dynamic_match(candidates).unwrap();
+        assert_eq!(matcher, SegmentMatcher::CatchAll("slug".into()));
+        assert_eq!(value, "catch-all");
+    }
+
+    #[test]
+    fn pick_best_dynamic_match_ignores_static_names() {
+        let candidates = vec![("blog", "static"), ("[...slug]", "catch-all")];
+        let (matcher, _) = pick_best_dynamic_match(candidates).unwrap();
+        assert_eq!(matcher, SegmentMatcher::CatchAll("slug".into()));
+
+        let only_static = vec![("blog", "static")];
+        assert!(pick_best_dynamic_match(only_static).is_none());
+    }
+
+    #[test]
+    fn split_pathname_trims_and_drops_empty_segments() {
+        assert_eq!(split_pathname("/blog/hello-world/"), vec!["blog", "hello-world"]);
+        assert_eq!(split_pathname("blog/hello-world"), vec!["blog", "hello-world"]);
+        assert_eq!(split_pathname("/"), Vec::<&str>::new());
+        assert_eq!(split_pathname(""), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn split_pathname_is_trailing_slash_invariant() {
+        assert_eq!(split_pathname("/blog/post"), split_pathname("/blog/post/"));
+    }
+
+    fn headers_message(status_code: u16) -> Vec<u8> {
+        serde_json::to_vec(&serde_json::json!({
+            "type": "middleware-headers",
+            "data": { "statusCode": status_code, "headers": ["x-test: 1"] },
+        }))
+        .unwrap()
+    }
+
+    fn body_message(data: &[u8]) -> Vec<u8> {
+        serde_json::to_vec(&serde_json::json!({
+            "type": "middleware-body",
+            "data": data,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn consume_middleware_stream_forwards_body_in_order_after_headers() {
+        let (tx, rx) = async_channel::unbounded();
+        tx.send(Ok(Bytes::from(headers_message(200)))).await.unwrap();
+        tx.send(Ok(Bytes::from(body_message(b"hello"))))
+            .await
+            .unwrap();
+        tx.send(Ok(Bytes::from(body_message(b"world"))))
+            .await
+            .unwrap();
+        drop(tx);
+
+        let RouterResult::StreamingMiddleware(headers, body) =
+            consume_middleware_stream(rx).await.unwrap()
+        else {
+            panic!("expected StreamingMiddleware");
+        };
+        assert_eq!(headers.status_code, 200);
+        assert_eq!(body.recv().await.unwrap().unwrap(), Bytes::from("hello"));
+        assert_eq!(body.recv().await.unwrap().unwrap(), Bytes::from("world"));
+        assert!(body.recv().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn consume_middleware_stream_downgrades_on_malformed_headers() {
+        let (tx, rx) = async_channel::unbounded();
+        tx.send(Ok(Bytes::from_static(b"not json"))).await.unwrap();
+        drop(tx);
+
+        assert_eq!(
+            consume_middleware_stream(rx).await.unwrap(),
+            RouterResult::Error
+        );
+    }
+
+    #[tokio::test]
+    async fn consume_middleware_stream_downgrades_on_missing_headers() {
+        let (tx, rx): (Sender<Result<Bytes, SharedError>>, _) = async_channel::unbounded();
+        drop(tx);
+
+        assert_eq!(
+            consume_middleware_stream(rx).await.unwrap(),
+            RouterResult::Error
+        );
+    }
+
+    #[tokio::test]
+    async fn forward_middleware_body_forwards_propagated_error() {
+        let (incoming_tx, incoming_rx) = async_channel::unbounded();
+        let (body_tx, body_rx) = async_channel::unbounded();
+        incoming_tx
+            .send(Err(SharedError::new(anyhow!("upstream broke"))))
+            .await
+            .unwrap();
+        drop(incoming_tx);
+
+        forward_middleware_body(incoming_rx, body_tx).await;
+
+        assert!(body_rx.recv().await.unwrap().is_err());
+        assert!(body_rx.recv().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn forward_middleware_body_forwards_error_on_malformed_chunk() {
+        let (incoming_tx, incoming_rx) = async_channel::unbounded();
+        let (body_tx, body_rx) = async_channel::unbounded();
+        incoming_tx
+            .send(Ok(Bytes::from_static(b"not json")))
+            .await
+            .unwrap();
+        drop(incoming_tx);
+
+        forward_middleware_body(incoming_rx, body_tx).await;
+
+        assert!(body_rx.recv().await.unwrap().is_err());
+        assert!(body_rx.recv().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn forward_middleware_body_forwards_error_on_unexpected_message_and_stops() {
+        let (incoming_tx, incoming_rx) = async_channel::unbounded();
+        let (body_tx, body_rx) = async_channel::unbounded();
+        incoming_tx
+            .send(Ok(Bytes::from(headers_message(200))))
+            .await
+            .unwrap();
+        incoming_tx
+            .send(Ok(Bytes::from(body_message(b"too-late"))))
+            .await
+            .unwrap();
+        drop(incoming_tx);
+
+        forward_middleware_body(incoming_rx, body_tx).await;
+
+        assert!(body_rx.recv().await.unwrap().is_err());
+        assert!(body_rx.recv().await.is_err());
+    }
 }